@@ -1,13 +1,23 @@
 use signal_hook::{consts::SIGINT, consts::SIGTERM, iterator::Signals};
 use std::{
-    sync::{atomic::AtomicBool, Arc},
+    collections::HashMap,
+    str::FromStr,
+    sync::{atomic::AtomicBool, Arc, Mutex},
     thread::sleep,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use argh::FromArgs;
-use logqtt::{client::LogqttClient, journal::JournalAdapter, LogAdapter};
-use rumqttc::{Connection, ConnectionError, MqttOptions};
+use logqtt::{
+    client::{ExpiryTable, LogqttClient, MqttProtocol, ReconnectPolicy, Transport},
+    filter::{glob_to_regex, FieldMatcher, Filter},
+    journal::JournalAdapter,
+    ring_buffer::RingBuffer,
+    supervisor::Supervisor,
+    LogAdapter, LogItem, LogLevel,
+};
+use regex::Regex;
+use rumqttc::{v5::MqttOptions as MqttOptionsV5, Connection, ConnectionError, MqttOptions};
 use systemd::{journal::OpenOptions, JournalSeek};
 
 /// journald to MQTT
@@ -39,6 +49,59 @@ struct Args {
     #[argh(option, long = "id", default = "hostname()")]
     id: String,
 
+    /// MQTT protocol version, "v4" or "v5" (default: v4)
+    #[argh(option, long = "protocol", default = "MqttProtocol::V4")]
+    protocol: MqttProtocol,
+
+    /// v5-only: max live topic aliases to hand out (default: 16, 0 disables aliasing)
+    #[argh(option, long = "topic-alias-max", default = "16")]
+    topic_alias_max: u16,
+
+    /// v5-only: per-level message expiry, repeatable, e.g. "debug=60" (seconds)
+    #[argh(option, long = "expiry")]
+    expiry: Vec<ExpiryArg>,
+
+    /// minimum broker reconnect backoff in milliseconds (default: 250)
+    #[argh(option, long = "backoff-min-ms", default = "250")]
+    backoff_min_ms: u64,
+
+    /// maximum broker reconnect backoff in milliseconds (default: 30000)
+    #[argh(option, long = "backoff-max-ms", default = "30_000")]
+    backoff_max_ms: u64,
+
+    /// give up after this many consecutive reconnect attempts (default: unlimited)
+    #[argh(option, long = "max-retries")]
+    max_retries: Option<u32>,
+
+    /// offline ring buffer capacity in bytes (default: 4 MiB)
+    #[argh(option, long = "buffer-bytes", default = "4 * 1024 * 1024")]
+    buffer_bytes: usize,
+
+    /// publish a synthetic log entry reporting how many messages were
+    /// dropped from the offline buffer once the broker connection recovers
+    #[argh(switch, long = "publish-overflow-notices")]
+    publish_overflow_notices: bool,
+
+    /// drop entries less severe than this level (default: debug, i.e. no filtering)
+    #[argh(option, long = "min-severity", default = "LogLevel::Debug")]
+    min_severity: LogLevel,
+
+    /// only publish units matching this glob, repeatable (default: all units)
+    #[argh(option, long = "include-unit")]
+    include_unit: Vec<UnitGlobArg>,
+
+    /// drop units matching this glob, repeatable
+    #[argh(option, long = "exclude-unit")]
+    exclude_unit: Vec<UnitGlobArg>,
+
+    /// drop entries unless journal field KEY equals VALUE, repeatable, e.g. "_PID=1"
+    #[argh(option, long = "match-field")]
+    match_field: Vec<FieldEqArg>,
+
+    /// drop entries unless journal field KEY matches the regex PATTERN, repeatable
+    #[argh(option, long = "match-field-regex")]
+    match_field_regex: Vec<FieldRegexArg>,
+
     /// use syslog formatting for log
     #[argh(switch, long = "syslog")]
     syslog: bool,
@@ -49,23 +112,54 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_logging(args.syslog);
     log::info!("ID: {}", args.id);
     log::info!("Broker: {}:{}", args.host, args.port);
-    let (client, connection) =
-        rumqttc::Client::new(MqttOptions::new(args.id, args.host, args.port), 32);
-    let mut client = LogqttClient::new(client, args.base_topic);
+    log::info!("Protocol: {:?}", args.protocol);
+
+    let (transport, connection) = build_transport(args.protocol, &args.id, &args.host, args.port);
+    let client = Arc::new(Mutex::new(LogqttClient::new(
+        transport,
+        args.base_topic,
+        expiry_table(&args.expiry),
+        args.topic_alias_max,
+    )));
 
     let mut signals = Signals::new([SIGINT, SIGTERM])?;
     let mut journal = JournalAdapter::open(open_options(), seek_now())?;
     let should_run = Arc::new(AtomicBool::new(true));
+    let supervisor = Supervisor::new(should_run.clone());
 
     let should_run_clone = should_run.clone();
-    std::thread::spawn(move || {
+    supervisor.spawn("signal-handler", move || {
         let _ = signals.forever().next();
         log::warn!("Close signaled");
         should_run_clone.store(false, std::sync::atomic::Ordering::Relaxed);
     });
 
+    let policy = ReconnectPolicy::new(
+        Duration::from_millis(args.backoff_min_ms),
+        Duration::from_millis(args.backoff_max_ms),
+        args.max_retries,
+    );
+    let filter = build_filter(&args);
+    let connected = Arc::new(AtomicBool::new(false));
+    let (protocol, id, host, port) = (args.protocol, args.id, args.host, args.port);
     let should_run_clone = should_run.clone();
-    let conn_loop_handle = std::thread::spawn(|| run_connection_loop(connection, should_run_clone));
+    let connected_clone = connected.clone();
+    let client_clone = client.clone();
+    let conn_loop_handle = supervisor.spawn("connection-loop", move || {
+        if let Err(err) = run_connection_loop(
+            connection,
+            client_clone,
+            policy,
+            move || build_transport(protocol, &id, &host, port),
+            should_run_clone,
+            connected_clone,
+        ) {
+            log::error!("Connection loop exited with error: {}", err);
+        }
+    });
+
+    let mut buffer: RingBuffer<LogItem> = RingBuffer::new(args.buffer_bytes);
+    let local_hostname = hostname();
     while should_run.load(std::sync::atomic::Ordering::Relaxed) {
         if conn_loop_handle.is_finished() {
             break;
@@ -73,7 +167,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         match journal.try_recv() {
             Ok(log_item) => {
-                client.push(log_item)?;
+                let empty_fields = HashMap::new();
+                let fields = log_item.fields.as_ref().unwrap_or(&empty_fields);
+                if !filter.accept(&log_item, fields) {
+                    continue;
+                }
+
+                // Only publish directly when there's no backlog ahead of this entry; otherwise
+                // buffer it too so the drain below keeps entries in arrival order.
+                if connected.load(std::sync::atomic::Ordering::Relaxed) && buffer.is_empty() {
+                    let mut guard = client.lock().expect("client mutex poisoned");
+                    if let Err(err) = guard.push(&log_item) {
+                        log::error!("Failed to publish log item: {}; buffering", err);
+                        drop(guard);
+                        let evicted = buffer.push(log_item);
+                        if evicted > 0 {
+                            log::warn!("Offline buffer full; dropped {} log item(s)", evicted);
+                        }
+                    }
+                } else {
+                    let evicted = buffer.push(log_item);
+                    if evicted > 0 {
+                        log::warn!("Offline buffer full; dropped {} log item(s)", evicted);
+                    }
+                }
             }
             Err(err) => match &err {
                 logqtt::error::TryRecvError::NotReady => sleep(Duration::from_millis(100)),
@@ -83,31 +200,295 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 _ => return Err(err.into()),
             },
         }
+
+        if connected.load(std::sync::atomic::Ordering::Relaxed) && !buffer.is_empty() {
+            let mut guard = client.lock().expect("client mutex poisoned");
+            while let Some(log_item) = buffer.pop() {
+                if let Err(err) = guard.push(&log_item) {
+                    log::error!("Failed to publish buffered log item: {}; re-buffering", err);
+                    buffer.push_front(log_item);
+                    break;
+                }
+            }
+
+            let dropped = buffer.take_dropped_count();
+            if dropped > 0 {
+                log::warn!(
+                    "{} log item(s) were dropped while disconnected from the broker",
+                    dropped
+                );
+                if args.publish_overflow_notices {
+                    let overflow_item = overflow_log_item(local_hostname.clone(), dropped);
+                    if let Err(err) = guard.push(&overflow_item) {
+                        log::error!("Failed to publish offline-buffer overflow notice: {}", err);
+                    }
+                }
+            }
+        }
     }
 
     log::info!("Closing");
 
+    if supervisor.panicked() {
+        return Err("a worker thread panicked".into());
+    }
+
     Ok(())
 }
 
-fn run_connection_loop(
-    mut connection: Connection,
+fn overflow_log_item(hostname: String, dropped: u64) -> LogItem {
+    LogItem {
+        hostname,
+        unit: "logqtt".to_owned(),
+        timestamp: SystemTime::now(),
+        level: LogLevel::Warning,
+        message: format!("{} log entries were dropped from the offline buffer", dropped),
+        fields: None,
+    }
+}
+
+enum MqttConnection {
+    V4(Connection),
+    V5(rumqttc::v5::Connection),
+}
+
+fn build_transport(protocol: MqttProtocol, id: &str, host: &str, port: u16) -> (Transport, MqttConnection) {
+    match protocol {
+        MqttProtocol::V4 => {
+            let (client, connection) = rumqttc::Client::new(MqttOptions::new(id, host, port), 32);
+            (Transport::V4(client), MqttConnection::V4(connection))
+        }
+        MqttProtocol::V5 => {
+            let (client, connection) =
+                rumqttc::v5::Client::new(MqttOptionsV5::new(id, host, port), 32);
+            (Transport::V5(client), MqttConnection::V5(connection))
+        }
+    }
+}
+
+fn run_connection_loop<F>(
+    mut connection: MqttConnection,
+    client: Arc<Mutex<LogqttClient>>,
+    policy: ReconnectPolicy,
+    rebuild: F,
     should_run: Arc<AtomicBool>,
-) -> Result<(), ConnectionError> {
-    for notification in connection.iter() {
-        let event = notification?;
-        log::debug!("{:?}", event);
+    connected: Arc<AtomicBool>,
+) -> Result<(), ConnectionError>
+where
+    F: Fn() -> (Transport, MqttConnection),
+{
+    let mut attempt = 0u32;
+
+    loop {
+        let err = match drive_connection(&mut connection, &should_run, &connected, &client) {
+            Ok(()) => {
+                log::info!("Connection loop done");
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+        // A ConnAck since the last failure breaks the run of failed attempts.
+        if connected.swap(false, std::sync::atomic::Ordering::Relaxed) {
+            attempt = 0;
+        }
 
         if !should_run.load(std::sync::atomic::Ordering::Relaxed) {
-            break;
+            return Ok(());
         }
+
+        if policy.exhausted(attempt) {
+            log::error!(
+                "Giving up on broker reconnection after {} attempt(s): {}",
+                attempt + 1,
+                err
+            );
+            should_run.store(false, std::sync::atomic::Ordering::Relaxed);
+            return Err(err);
+        }
+
+        let backoff = policy.backoff(attempt);
+        log::warn!(
+            "Broker connection lost ({}); reconnecting in {:?} (attempt {})",
+            err,
+            backoff,
+            attempt + 1
+        );
+        sleep(backoff);
+
+        let (transport, new_connection) = rebuild();
+        client
+            .lock()
+            .expect("client mutex poisoned")
+            .reset_transport(transport);
+        connection = new_connection;
+        attempt += 1;
     }
+}
+
+fn drive_connection(
+    connection: &mut MqttConnection,
+    should_run: &Arc<AtomicBool>,
+    connected: &Arc<AtomicBool>,
+    client: &Arc<Mutex<LogqttClient>>,
+) -> Result<(), ConnectionError> {
+    match connection {
+        MqttConnection::V4(connection) => {
+            for notification in connection.iter() {
+                let event = notification?;
+                log::debug!("{:?}", event);
 
-    log::info!("Connection loop done");
+                if matches!(event, rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) {
+                    connected.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+
+                if !should_run.load(std::sync::atomic::Ordering::Relaxed) {
+                    break;
+                }
+            }
+        }
+        MqttConnection::V5(connection) => {
+            for notification in connection.iter() {
+                let event = notification?;
+                log::debug!("{:?}", event);
+
+                if let rumqttc::v5::Event::Incoming(rumqttc::v5::mqttbytes::v5::Packet::ConnAck(
+                    connack,
+                )) = &event
+                {
+                    connected.store(true, std::sync::atomic::Ordering::Relaxed);
+
+                    if let Some(broker_max) =
+                        connack.properties.as_ref().and_then(|p| p.topic_alias_max)
+                    {
+                        client
+                            .lock()
+                            .expect("client mutex poisoned")
+                            .cap_topic_alias_max(broker_max);
+                    }
+                }
+
+                if !should_run.load(std::sync::atomic::Ordering::Relaxed) {
+                    break;
+                }
+            }
+        }
+    }
 
     Ok(())
 }
 
+fn expiry_table(args: &[ExpiryArg]) -> ExpiryTable {
+    let mut table = ExpiryTable::new();
+    for arg in args {
+        table.set(arg.level, arg.ttl);
+    }
+    table
+}
+
+#[derive(Clone, Debug)]
+struct ExpiryArg {
+    level: LogLevel,
+    ttl: Duration,
+}
+
+impl FromStr for ExpiryArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (level, secs) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected LEVEL=SECONDS, got {:?}", s))?;
+        let secs: u64 = secs
+            .parse()
+            .map_err(|_| format!("invalid TTL seconds: {:?}", secs))?;
+
+        Ok(Self {
+            level: level.parse()?,
+            ttl: Duration::from_secs(secs),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct UnitGlobArg(Regex);
+
+impl FromStr for UnitGlobArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        glob_to_regex(s)
+            .map(UnitGlobArg)
+            .map_err(|e| format!("invalid unit glob {:?}: {}", s, e))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct FieldEqArg {
+    key: String,
+    value: String,
+}
+
+impl FromStr for FieldEqArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected KEY=VALUE, got {:?}", s))?;
+
+        Ok(Self {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct FieldRegexArg {
+    key: String,
+    pattern: Regex,
+}
+
+impl FromStr for FieldRegexArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, pattern) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected KEY=PATTERN, got {:?}", s))?;
+
+        Ok(Self {
+            key: key.to_owned(),
+            pattern: Regex::new(pattern).map_err(|e| format!("invalid regex {:?}: {}", pattern, e))?,
+        })
+    }
+}
+
+fn build_filter(args: &Args) -> Filter {
+    let mut filter = Filter::new(args.min_severity);
+
+    for unit in &args.include_unit {
+        filter.include_unit(unit.0.clone());
+    }
+    for unit in &args.exclude_unit {
+        filter.exclude_unit(unit.0.clone());
+    }
+    for field in &args.match_field {
+        filter.match_field(FieldMatcher::Equals {
+            key: field.key.clone(),
+            value: field.value.clone(),
+        });
+    }
+    for field in &args.match_field_regex {
+        filter.match_field(FieldMatcher::Regex {
+            key: field.key.clone(),
+            pattern: field.pattern.clone(),
+        });
+    }
+
+    filter
+}
+
 fn hostname() -> String {
     whoami::fallible::hostname().expect("failed to get hostname")
 }
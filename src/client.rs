@@ -1,21 +1,184 @@
-use std::time::UNIX_EPOCH;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use rumqttc::{Client, ClientError, QoS};
+use rumqttc::{
+    v5::{mqttbytes::v5::PublishProperties, Client as ClientV5},
+    Client, ClientError, QoS,
+};
 use serde_json::json;
 
-use crate::LogItem;
+use crate::{LogItem, LogLevel};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MqttProtocol {
+    V4,
+    V5,
+}
+
+impl std::str::FromStr for MqttProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "v4" => Ok(MqttProtocol::V4),
+            "v5" => Ok(MqttProtocol::V5),
+            other => Err(format!("unknown MQTT protocol: {:?}", other)),
+        }
+    }
+}
+
+pub enum Transport {
+    V4(Client),
+    V5(ClientV5),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExpiryTable([Option<Duration>; 8]);
+
+impl ExpiryTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, level: LogLevel, ttl: Duration) {
+        self.0[level.rank()] = ttl.into();
+    }
+
+    pub fn get(&self, level: LogLevel) -> Option<Duration> {
+        self.0[level.rank()]
+    }
+}
+
+enum TopicAliasResolution {
+    New(u16),
+    Cached(u16),
+    Unaliased,
+}
+
+struct TopicAliasTable {
+    max_aliases: u16,
+    aliases: HashMap<String, u16>,
+    recency: VecDeque<String>,
+    next_alias: u16,
+}
+
+impl TopicAliasTable {
+    fn new(max_aliases: u16) -> Self {
+        Self {
+            max_aliases,
+            aliases: HashMap::new(),
+            recency: VecDeque::new(),
+            next_alias: 1,
+        }
+    }
+
+    fn resolve(&mut self, topic: &str) -> TopicAliasResolution {
+        if self.max_aliases == 0 {
+            return TopicAliasResolution::Unaliased;
+        }
+
+        if let Some(&alias) = self.aliases.get(topic) {
+            self.touch(topic);
+            return TopicAliasResolution::Cached(alias);
+        }
+
+        // At capacity: reclaim the least-recently-used alias rather than growing past max_aliases.
+        let alias = if self.aliases.len() as u16 >= self.max_aliases {
+            let evicted = self
+                .recency
+                .pop_front()
+                .expect("alias table is non-empty when at capacity");
+            self.aliases
+                .remove(&evicted)
+                .expect("evicted topic has an alias entry")
+        } else {
+            let alias = self.next_alias;
+            self.next_alias += 1;
+            alias
+        };
+
+        self.aliases.insert(topic.to_owned(), alias);
+        self.recency.push_back(topic.to_owned());
+        TopicAliasResolution::New(alias)
+    }
+
+    fn touch(&mut self, topic: &str) {
+        if let Some(pos) = self.recency.iter().position(|t| t == topic) {
+            let entry = self.recency.remove(pos).expect("position found above");
+            self.recency.push_back(entry);
+        }
+    }
+
+    fn cap(&mut self, broker_max: u16) {
+        if broker_max >= self.max_aliases {
+            return;
+        }
+
+        self.max_aliases = broker_max;
+        while self.aliases.len() as u16 > self.max_aliases {
+            match self.recency.pop_front() {
+                Some(evicted) => {
+                    self.aliases.remove(&evicted);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // Drops a mapping the broker never actually acknowledged (e.g. its publish failed),
+    // so the next attempt re-sends the full topic instead of a dangling alias.
+    fn forget(&mut self, topic: &str) {
+        if self.aliases.remove(topic).is_some() {
+            if let Some(pos) = self.recency.iter().position(|t| t == topic) {
+                self.recency.remove(pos);
+            }
+        }
+    }
+}
 
 pub struct LogqttClient {
-    client: Client,
+    transport: Transport,
     base_topic: String,
+    expiry: ExpiryTable,
+    aliases: Option<TopicAliasTable>,
+    configured_topic_alias_max: u16,
 }
 
 impl LogqttClient {
-    pub fn new(client: Client, base_topic: String) -> Self {
-        Self { client, base_topic }
+    pub fn new(transport: Transport, base_topic: String, expiry: ExpiryTable, topic_alias_max: u16) -> Self {
+        let aliases = match transport {
+            Transport::V4(_) => None,
+            Transport::V5(_) => Some(TopicAliasTable::new(topic_alias_max)),
+        };
+
+        Self {
+            transport,
+            base_topic,
+            expiry,
+            aliases,
+            configured_topic_alias_max: topic_alias_max,
+        }
+    }
+
+    // v5 topic aliases are broker-local, so the alias table is reset along with the transport,
+    // back up to the configured max — a lower cap learned from a prior broker doesn't carry over.
+    pub fn reset_transport(&mut self, transport: Transport) {
+        self.aliases = match transport {
+            Transport::V4(_) => None,
+            Transport::V5(_) => Some(TopicAliasTable::new(self.configured_topic_alias_max)),
+        };
+        self.transport = transport;
+    }
+
+    pub fn cap_topic_alias_max(&mut self, broker_max: u16) {
+        if let Some(aliases) = self.aliases.as_mut() {
+            aliases.cap(broker_max);
+        }
     }
 
-    pub fn push(&mut self, log_item: LogItem) -> Result<(), ClientError> {
+    pub fn push(&mut self, log_item: &LogItem) -> Result<(), ClientError> {
         let topic = format!(
             "{}/{}/{}/{}",
             self.base_topic,
@@ -41,6 +204,79 @@ impl LogqttClient {
         // serialization should never fail; safe to unwrap
         .expect("failed to serialize JSON");
 
-        self.client.publish(topic, QoS::AtLeastOnce, false, payload)
+        match &mut self.transport {
+            Transport::V4(client) => client.publish(topic, QoS::AtLeastOnce, false, payload),
+            Transport::V5(client) => {
+                let mut properties = PublishProperties::default();
+                if let Some(ttl) = self.expiry.get(log_item.level) {
+                    properties.message_expiry_interval = Some(ttl.as_secs() as u32);
+                }
+
+                let aliases = self
+                    .aliases
+                    .as_mut()
+                    .expect("topic alias table present for v5 transport");
+                let resolution = aliases.resolve(&topic);
+                let topic_to_send = match resolution {
+                    TopicAliasResolution::New(alias) => {
+                        properties.topic_alias = Some(alias);
+                        topic.clone()
+                    }
+                    TopicAliasResolution::Cached(alias) => {
+                        properties.topic_alias = Some(alias);
+                        String::new()
+                    }
+                    TopicAliasResolution::Unaliased => topic.clone(),
+                };
+
+                let result =
+                    client.publish_with_properties(topic_to_send, QoS::AtLeastOnce, false, payload, properties);
+                if result.is_err() && matches!(resolution, TopicAliasResolution::New(_)) {
+                    // The broker never got a chance to learn this mapping; don't leave it
+                    // stuck as Cached for a later retry, which would send only a dangling alias.
+                    aliases.forget(&topic);
+                }
+
+                result
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    min_backoff: Duration,
+    max_backoff: Duration,
+    max_retries: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    pub fn new(min_backoff: Duration, max_backoff: Duration, max_retries: Option<u32>) -> Self {
+        Self {
+            min_backoff,
+            max_backoff,
+            max_retries,
+        }
+    }
+
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.min(20)).unwrap_or(u32::MAX);
+        let capped = self.min_backoff.saturating_mul(factor).min(self.max_backoff);
+        capped
+            .mul_f64(0.8 + 0.4 * jitter_fraction())
+            .max(self.min_backoff)
+            .min(self.max_backoff)
     }
+
+    pub fn exhausted(&self, attempt: u32) -> bool {
+        self.max_retries.map_or(false, |max| attempt >= max)
+    }
+}
+
+fn jitter_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    (nanos % 1_000) as f64 / 1_000.0
 }
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::{LogItem, LogLevel};
+
+pub enum FieldMatcher {
+    Equals { key: String, value: String },
+    Regex { key: String, pattern: Regex },
+}
+
+impl FieldMatcher {
+    fn matches(&self, fields: &HashMap<String, String>) -> bool {
+        match self {
+            FieldMatcher::Equals { key, value } => {
+                fields.get(key).map_or(false, |v| v == value)
+            }
+            FieldMatcher::Regex { key, pattern } => {
+                fields.get(key).map_or(false, |v| pattern.is_match(v))
+            }
+        }
+    }
+}
+
+pub struct Filter {
+    min_severity: LogLevel,
+    include_units: Vec<Regex>,
+    exclude_units: Vec<Regex>,
+    field_matchers: Vec<FieldMatcher>,
+}
+
+impl Filter {
+    pub fn new(min_severity: LogLevel) -> Self {
+        Self {
+            min_severity,
+            include_units: Vec::new(),
+            exclude_units: Vec::new(),
+            field_matchers: Vec::new(),
+        }
+    }
+
+    pub fn include_unit(&mut self, pattern: Regex) {
+        self.include_units.push(pattern);
+    }
+
+    pub fn exclude_unit(&mut self, pattern: Regex) {
+        self.exclude_units.push(pattern);
+    }
+
+    pub fn match_field(&mut self, matcher: FieldMatcher) {
+        self.field_matchers.push(matcher);
+    }
+
+    pub fn accept(&self, log_item: &LogItem, extra_fields: &HashMap<String, String>) -> bool {
+        if log_item.level.rank() > self.min_severity.rank() {
+            return false;
+        }
+
+        if !self.include_units.is_empty()
+            && !self.include_units.iter().any(|p| p.is_match(&log_item.unit))
+        {
+            return false;
+        }
+
+        if self.exclude_units.iter().any(|p| p.is_match(&log_item.unit)) {
+            return false;
+        }
+
+        self.field_matchers.iter().all(|m| m.matches(extra_fields))
+    }
+}
+
+pub fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut pattern = String::with_capacity(glob.len() + 2);
+    pattern.push('^');
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c if "\\.+()|[]{}^$".contains(c) => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+    pattern.push('$');
+
+    Regex::new(&pattern)
+}
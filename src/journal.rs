@@ -1,5 +1,6 @@
 use std::{
     borrow::Cow,
+    collections::HashMap,
     thread::sleep,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
@@ -72,7 +73,10 @@ fn entry_to_log_item(entry: JournalRecord) -> Result<LogItem, Cow<'static, str>>
     let mut message = None;
     let mut timestamp = None;
     let mut priority = None;
+    let mut fields = HashMap::new();
     for (k, v) in entry.into_iter() {
+        fields.insert(k.clone(), v.clone());
+
         match k.as_str() {
             "MESSAGE" => message = Some(v),
             "PRIORITY" => {
@@ -109,6 +113,7 @@ fn entry_to_log_item(entry: JournalRecord) -> Result<LogItem, Cow<'static, str>>
         level: priority.ok_or("missing priority field from journal entry")?,
         message: message.ok_or("missing message field from journal entry")?,
         timestamp: timestamp.unwrap_or(SystemTime::now()),
+        fields: Some(fields),
     })
 }
 
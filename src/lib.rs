@@ -1,6 +1,9 @@
-use std::time::SystemTime;
+use std::{collections::HashMap, time::SystemTime};
 
 pub mod client;
+pub mod filter;
+pub mod ring_buffer;
+pub mod supervisor;
 
 #[cfg(feature = "journal-adapter")]
 pub mod journal;
@@ -17,9 +20,11 @@ pub struct LogItem {
     pub timestamp: SystemTime,
     pub level: LogLevel,
     pub message: String,
+    // Raw journal fields, for matching beyond what's parsed into the fields above.
+    pub fields: Option<HashMap<String, String>>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LogLevel {
     Emergency,
     Alert,
@@ -31,6 +36,23 @@ pub enum LogLevel {
     Debug,
 }
 
+impl LogLevel {
+    /// Syslog/journald priority ranking: 0 is most severe (`Emergency`), 7 is
+    /// least severe (`Debug`). Lower rank sorts as more severe.
+    pub fn rank(&self) -> usize {
+        match self {
+            LogLevel::Emergency => 0,
+            LogLevel::Alert => 1,
+            LogLevel::Critical => 2,
+            LogLevel::Error => 3,
+            LogLevel::Warning => 4,
+            LogLevel::Notice => 5,
+            LogLevel::Info => 6,
+            LogLevel::Debug => 7,
+        }
+    }
+}
+
 impl AsRef<str> for LogLevel {
     fn as_ref(&self) -> &str {
         match self {
@@ -46,6 +68,24 @@ impl AsRef<str> for LogLevel {
     }
 }
 
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "emergency" => Ok(LogLevel::Emergency),
+            "alert" => Ok(LogLevel::Alert),
+            "critical" => Ok(LogLevel::Critical),
+            "error" => Ok(LogLevel::Error),
+            "warning" => Ok(LogLevel::Warning),
+            "notice" => Ok(LogLevel::Notice),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            other => Err(format!("unknown log level: {:?}", other)),
+        }
+    }
+}
+
 pub mod error {
     use std::{borrow::Cow, fmt::Display};
 
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+use crate::LogItem;
+
+pub trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl ByteSize for LogItem {
+    fn byte_size(&self) -> usize {
+        let fields_size = self
+            .fields
+            .as_ref()
+            .map(|fields| fields.iter().map(|(k, v)| k.len() + v.len()).sum())
+            .unwrap_or(0);
+
+        self.hostname.len()
+            + self.unit.len()
+            + self.message.len()
+            + fields_size
+            + std::mem::size_of::<Self>()
+    }
+}
+
+pub struct RingBuffer<T: ByteSize> {
+    items: VecDeque<T>,
+    max_bytes: usize,
+    used_bytes: usize,
+    dropped: u64,
+}
+
+impl<T: ByteSize> RingBuffer<T> {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            max_bytes,
+            used_bytes: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: T) -> usize {
+        self.used_bytes += item.byte_size();
+        self.items.push_back(item);
+
+        // Evict oldest-first until back under budget, even the item just pushed.
+        let mut evicted = 0;
+        while self.used_bytes > self.max_bytes {
+            match self.items.pop_front() {
+                Some(front) => {
+                    self.used_bytes -= front.byte_size();
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+
+        self.dropped += evicted as u64;
+        evicted
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let item = self.items.pop_front()?;
+        self.used_bytes -= item.byte_size();
+        Some(item)
+    }
+
+    pub fn push_front(&mut self, item: T) {
+        self.used_bytes += item.byte_size();
+        self.items.push_front(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn take_dropped_count(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+}
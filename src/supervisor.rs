@@ -0,0 +1,53 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+pub struct Supervisor {
+    should_run: Arc<AtomicBool>,
+    panicked: Arc<AtomicBool>,
+}
+
+impl Supervisor {
+    pub fn new(should_run: Arc<AtomicBool>) -> Self {
+        Self {
+            should_run,
+            panicked: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn spawn<F>(&self, name: &'static str, f: F) -> JoinHandle<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let should_run = self.should_run.clone();
+        let panicked = self.panicked.clone();
+
+        thread::spawn(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                log::error!("Thread {:?} panicked: {}", name, panic_message(&payload));
+                panicked.store(true, Ordering::Relaxed);
+                should_run.store(false, Ordering::Relaxed);
+            }
+        })
+    }
+
+    pub fn panicked(&self) -> bool {
+        self.panicked.load(Ordering::Relaxed)
+    }
+}
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_owned()
+    }
+}